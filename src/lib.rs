@@ -1,10 +1,14 @@
 use jsonschema::{Draft, Retrieve, Uri, Validator};
 use napi::bindgen_prelude::*;
+use napi::JsFunction;
 use napi_derive::napi;
+use regex::Regex;
 use serde_json::Value;
 use std::cell::RefCell;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use mimalloc::MiMalloc;
 
@@ -16,9 +20,430 @@ thread_local! {
     static PARSE_BUF: RefCell<Vec<u8>> = RefCell::new(Vec::with_capacity(256 * 1024));
 }
 
-/// Custom schema retriever that uses pre-registered schemas
+/// Remote-fetch limits for `SchemaRetriever`: aborts an oversized body before
+/// it's fully buffered and bounds how long a stalled host can block compile.
+const REMOTE_SCHEMA_MAX_BYTES: u64 = 1024 * 1024;
+const REMOTE_SCHEMA_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Compiles a `fileMatch` glob (`*` for any run of characters, `?` for a
+/// single character) into an anchored regex.
+fn glob_to_regex(pattern: &str) -> std::result::Result<Regex, regex::Error> {
+    let mut re = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            c if "\\.+()|[]{}^$".contains(c) => {
+                re.push('\\');
+                re.push(c);
+            }
+            c => re.push(c),
+        }
+    }
+    re.push('$');
+    Regex::new(&re)
+}
+
+/// Rewrites OpenAPI 3.0-only keywords (`nullable`, `discriminator`) into
+/// plain JSON Schema in place, recursing into every nested subschema, so the
+/// rest of `compile` can build an ordinary Draft 4 validator from the result.
+fn apply_openapi_dialect(value: &mut Value) {
+    match value {
+        Value::Object(obj) => {
+            for (key, v) in obj.iter_mut() {
+                match key.as_str() {
+                    // Maps from names to subschemas: the names are not
+                    // keywords, so only the subschemas themselves are rewritten.
+                    "properties" | "patternProperties" | "definitions" | "$defs" => {
+                        if let Value::Object(subschemas) = v {
+                            for subschema in subschemas.values_mut() {
+                                apply_openapi_dialect(subschema);
+                            }
+                        }
+                    }
+                    // Instance data or the discriminator itself, not subschemas.
+                    "enum" | "const" | "default" | "example" | "discriminator" => {}
+                    _ => apply_openapi_dialect(v),
+                }
+            }
+            rewrite_openapi_nullable(obj);
+            rewrite_openapi_discriminator(obj);
+        }
+        Value::Array(arr) => {
+            for v in arr.iter_mut() {
+                apply_openapi_dialect(v);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `nullable: true` becomes an added `"null"` member of `type`, matching how
+/// OpenAPI 3.0 itself defines the keyword relative to Draft 4.
+fn rewrite_openapi_nullable(obj: &mut serde_json::Map<String, Value>) {
+    let Some(nullable) = obj.remove("nullable") else {
+        return;
+    };
+
+    if nullable != Value::Bool(true) {
+        return;
+    }
+
+    match obj.get_mut("type") {
+        Some(Value::String(t)) => {
+            let t = t.clone();
+            obj.insert("type".to_string(), serde_json::json!([t, "null"]));
+        }
+        Some(Value::Array(types)) => {
+            if !types.iter().any(|t| t == "null") {
+                types.push(Value::String("null".to_string()));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Converts a `discriminator`-annotated `oneOf`/`anyOf` into a dispatch
+/// keyed on the discriminator property, so a document is checked only
+/// against the branch its discriminator value selects instead of against
+/// every member. Each named branch becomes "the discriminator is not this
+/// name, or the variant holds" (`anyOf` of a `not` and the variant), the
+/// Draft 4 form of `if`/`then`, since Draft 4 has no `if`/`then`/`const`.
+/// `discriminator.mapping` gives the branch name for a `$ref` explicitly;
+/// otherwise the name is inferred from the `$ref`'s last path segment,
+/// matching OpenAPI's default convention.
+///
+/// Two correctness requirements the naive per-branch → `allOf` rewrite
+/// gets wrong: (1) a discriminator value that matches no known branch name
+/// must fail, not vacuously pass because no branch was selected, so the
+/// branches are guarded by an explicit `enum` check against every known
+/// name; (2) inline variants with no resolvable name can't be keyed by a
+/// discriminator value at all, so they must stay a disjunctive alternative
+/// (`oneOf`/`anyOf`) rather than being folded into the `allOf`, where they'd
+/// become an unconditionally required branch.
+fn rewrite_openapi_discriminator(obj: &mut serde_json::Map<String, Value>) {
+    let Some(discriminator) = obj.remove("discriminator") else {
+        return;
+    };
+
+    let property_name = match discriminator.get("propertyName").and_then(|v| v.as_str()) {
+        Some(name) => name.to_string(),
+        None => {
+            obj.insert("discriminator".to_string(), discriminator);
+            return;
+        }
+    };
+
+    let variants_key = if obj.contains_key("oneOf") {
+        "oneOf"
+    } else if obj.contains_key("anyOf") {
+        "anyOf"
+    } else {
+        obj.insert("discriminator".to_string(), discriminator);
+        return;
+    };
+
+    let variants = match obj.remove(variants_key) {
+        Some(Value::Array(variants)) => variants,
+        other => {
+            if let Some(other) = other {
+                obj.insert(variants_key.to_string(), other);
+            }
+            obj.insert("discriminator".to_string(), discriminator);
+            return;
+        }
+    };
+
+    let ref_to_name: HashMap<String, String> = discriminator
+        .get("mapping")
+        .and_then(|m| m.as_object())
+        .map(|m| {
+            m.iter()
+                .filter_map(|(name, r)| r.as_str().map(|r| (r.to_string(), name.clone())))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut named_branches: Vec<(String, Value)> = Vec::new();
+    let mut unnamed_variants: Vec<Value> = Vec::new();
+
+    for variant in variants {
+        let name = variant.get("$ref").and_then(|v| v.as_str()).and_then(|r| {
+            ref_to_name
+                .get(r)
+                .cloned()
+                .or_else(|| r.rsplit('/').next().map(|s| s.to_string()))
+        });
+
+        match name {
+            Some(name) => {
+                let mut properties = serde_json::Map::new();
+                properties.insert(property_name.clone(), serde_json::json!({ "enum": [name] }));
+
+                let mut selects_branch = serde_json::Map::new();
+                selects_branch.insert("properties".to_string(), Value::Object(properties));
+                selects_branch.insert(
+                    "required".to_string(),
+                    Value::Array(vec![Value::String(property_name.clone())]),
+                );
+
+                let branch = serde_json::json!({
+                    "anyOf": [{ "not": Value::Object(selects_branch) }, variant]
+                });
+                named_branches.push((name, branch));
+            }
+            None => unnamed_variants.push(variant),
+        }
+    }
+
+    if named_branches.is_empty() {
+        // Nothing to discriminate on; fall back to the original disjunction.
+        obj.insert(variants_key.to_string(), Value::Array(unnamed_variants));
+        return;
+    }
+
+    let known_names: Vec<Value> = named_branches
+        .iter()
+        .map(|(name, _)| Value::String(name.clone()))
+        .collect();
+
+    let mut guard_properties = serde_json::Map::new();
+    guard_properties.insert(property_name.clone(), serde_json::json!({ "enum": known_names }));
+
+    let mut guard = serde_json::Map::new();
+    guard.insert("properties".to_string(), Value::Object(guard_properties));
+    guard.insert(
+        "required".to_string(),
+        Value::Array(vec![Value::String(property_name)]),
+    );
+
+    let mut discriminated_all_of = vec![Value::Object(guard)];
+    discriminated_all_of.extend(named_branches.into_iter().map(|(_, branch)| branch));
+
+    if unnamed_variants.is_empty() {
+        // Every variant is discriminated: the enum guard plus per-branch
+        // checks apply directly.
+        obj.insert("allOf".to_string(), Value::Array(discriminated_all_of));
+    } else {
+        // Inline variants can't be keyed by a discriminator value, so they
+        // stay disjunctive alongside the discriminated block.
+        let mut discriminated_block = serde_json::Map::new();
+        discriminated_block.insert("allOf".to_string(), Value::Array(discriminated_all_of));
+
+        unnamed_variants.push(Value::Object(discriminated_block));
+        obj.insert(variants_key.to_string(), Value::Array(unnamed_variants));
+    }
+}
+
+/// Walk the JSON Schema applicator keywords (`properties`, `items`,
+/// `oneOf`/`anyOf`/`allOf`, `if`/`then`/`else`) and push a `schema_path`-style
+/// location string for every subschema reachable this way. Used by verbose
+/// output to annotate which applicator branches passed, not just which
+/// failed.
+fn collect_applicator_locations(schema: &Value, prefix: String, out: &mut Vec<String>) {
+    let Value::Object(map) = schema else {
+        return;
+    };
+
+    if let Some(Value::Object(properties)) = map.get("properties") {
+        for (key, subschema) in properties {
+            let location = format!("{}/properties/{}", prefix, key);
+            out.push(location.clone());
+            collect_applicator_locations(subschema, location, out);
+        }
+    }
+
+    if let Some(items) = map.get("items") {
+        let location = format!("{}/items", prefix);
+        out.push(location.clone());
+        collect_applicator_locations(items, location, out);
+    }
+
+    for keyword in ["oneOf", "anyOf", "allOf"] {
+        if let Some(Value::Array(variants)) = map.get(keyword) {
+            for (i, variant) in variants.iter().enumerate() {
+                let location = format!("{}/{}/{}", prefix, keyword, i);
+                out.push(location.clone());
+                collect_applicator_locations(variant, location, out);
+            }
+        }
+    }
+
+    for keyword in ["if", "then", "else"] {
+        if let Some(subschema) = map.get(keyword) {
+            let location = format!("{}/{}", prefix, keyword);
+            out.push(location.clone());
+            collect_applicator_locations(subschema, location, out);
+        }
+    }
+}
+
+/// Resolves a `draft_uri` argument to the `jsonschema` crate's `Draft` enum.
+fn parse_draft_uri(uri: &str) -> Option<Draft> {
+    match uri {
+        "http://json-schema.org/draft-04/schema#" => Some(Draft::Draft4),
+        "http://json-schema.org/draft-06/schema#" => Some(Draft::Draft6),
+        "http://json-schema.org/draft-07/schema#" => Some(Draft::Draft7),
+        "https://json-schema.org/draft/2019-09/schema" => Some(Draft::Draft201909),
+        "https://json-schema.org/draft/2020-12/schema" => Some(Draft::Draft202012),
+        _ => None,
+    }
+}
+
+/// The canonical `$schema` URI for a `Draft`, the inverse of `parse_draft_uri`.
+fn draft_meta_schema_uri(draft: Draft) -> &'static str {
+    match draft {
+        Draft::Draft4 => "http://json-schema.org/draft-04/schema#",
+        Draft::Draft6 => "http://json-schema.org/draft-06/schema#",
+        Draft::Draft7 => "http://json-schema.org/draft-07/schema#",
+        Draft::Draft201909 => "https://json-schema.org/draft/2019-09/schema",
+        Draft::Draft202012 => "https://json-schema.org/draft/2020-12/schema",
+        _ => "https://json-schema.org/draft/2020-12/schema",
+    }
+}
+
+/// Validates a schema document against the meta-schema for `draft` when the
+/// caller explicitly selected one via `draft_uri`; otherwise auto-detects
+/// the dialect from the schema's own `$schema` (defaulting to the latest
+/// draft `jsonschema` supports). Returns the failure `jsonschema::meta::validate`
+/// reports, in the same path-annotated `AjvError` shape as instance
+/// validation; that is at most one failure, and further failures in the
+/// same schema are not reported.
+fn validate_against_meta_schema(
+    schema: &Value,
+    draft: Option<Draft>,
+) -> std::result::Result<(), Vec<AjvError>> {
+    let schema_for_check = match draft {
+        Some(draft) => {
+            let mut schema = schema.clone();
+            if let Value::Object(ref mut map) = schema {
+                map.insert(
+                    "$schema".to_string(),
+                    Value::String(draft_meta_schema_uri(draft).to_string()),
+                );
+            }
+            schema
+        }
+        None => schema.clone(),
+    };
+
+    let errors: Vec<AjvError> = jsonschema::meta::validate(&schema_for_check)
+        .err()
+        .into_iter()
+        .map(|e| AjvError {
+            message: e.to_string(),
+            instance_path: e.instance_path.to_string(),
+            schema_path: e.schema_path.to_string(),
+        })
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Custom schema retriever that resolves pre-registered schemas first, and
+/// optionally falls back to fetching unregistered `http(s)` refs over the
+/// network, subject to a host allowlist and a shared, size-bounded cache.
 struct SchemaRetriever {
     schemas: Arc<HashMap<String, Value>>,
+    load_remote: bool,
+    allowed_hosts: Arc<Vec<String>>,
+    remote_cache: Arc<Mutex<HashMap<String, Value>>>,
+}
+
+/// Upper bound on redirect hops `retrieve_remote` will follow, each of which
+/// is re-checked against `allowed_hosts` individually.
+const REMOTE_SCHEMA_MAX_REDIRECTS: u32 = 5;
+
+/// Upper bound on the number of distinct remote schema URIs `remote_cache`
+/// will hold. Without this, a long-running `Ajv` instance that resolves
+/// many distinct external `$ref` hosts over its lifetime would grow the
+/// cache without bound. When full, an arbitrary entry is evicted to make
+/// room - good enough since the cache exists to avoid redundant fetches,
+/// not to guarantee any particular retention policy.
+const REMOTE_SCHEMA_CACHE_MAX_ENTRIES: usize = 256;
+
+fn uri_host(uri_str: &str) -> &str {
+    uri_str
+        .split("://")
+        .nth(1)
+        .and_then(|rest| rest.split(['/', '#']).next())
+        .unwrap_or("")
+}
+
+impl SchemaRetriever {
+    fn retrieve_remote(
+        &self,
+        uri_str: &str,
+    ) -> std::result::Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(schema) = self.remote_cache.lock().unwrap().get(uri_str) {
+            return std::result::Result::Ok(schema.clone());
+        }
+
+        // Redirects are followed manually (agent built with `redirects(0)`)
+        // so each hop's host can be checked against the allowlist before
+        // it's fetched - an allowlisted host redirecting to e.g. a metadata
+        // IP must not bypass the allowlist.
+        let agent = ureq::AgentBuilder::new().redirects(0).build();
+        let mut current = uri_str.to_string();
+
+        for _ in 0..=REMOTE_SCHEMA_MAX_REDIRECTS {
+            let host = uri_host(&current);
+            if !self.allowed_hosts.iter().any(|allowed| allowed == host) {
+                return std::result::Result::Err(
+                    format!("Host not in allowedHosts: {}", host).into(),
+                );
+            }
+
+            let response = agent
+                .get(&current)
+                .timeout(REMOTE_SCHEMA_TIMEOUT)
+                .call()
+                .map_err(|e| format!("Failed to fetch {}: {}", current, e))?;
+
+            if (300..400).contains(&response.status()) {
+                let location = response
+                    .header("Location")
+                    .ok_or_else(|| format!("Redirect from {} had no Location header", current))?;
+
+                if !location.starts_with("http://") && !location.starts_with("https://") {
+                    return std::result::Result::Err(
+                        format!("Relative redirect from {} is not supported: {}", current, location).into(),
+                    );
+                }
+
+                current = location.to_string();
+                continue;
+            }
+
+            let mut body = String::new();
+            response
+                .into_reader()
+                .take(REMOTE_SCHEMA_MAX_BYTES)
+                .read_to_string(&mut body)
+                .map_err(|e| format!("Failed to read {}: {}", current, e))?;
+
+            let schema: Value = serde_json::from_str(&body)
+                .map_err(|e| format!("Invalid remote schema at {}: {}", current, e))?;
+
+            {
+                let mut cache = self.remote_cache.lock().unwrap();
+                if cache.len() >= REMOTE_SCHEMA_CACHE_MAX_ENTRIES && !cache.contains_key(uri_str) {
+                    if let Some(key) = cache.keys().next().cloned() {
+                        cache.remove(&key);
+                    }
+                }
+                cache.insert(uri_str.to_string(), schema.clone());
+            }
+
+            return std::result::Result::Ok(schema);
+        }
+
+        std::result::Result::Err(format!("Too many redirects fetching {}", uri_str).into())
+    }
 }
 
 impl Retrieve for SchemaRetriever {
@@ -45,24 +470,278 @@ impl Retrieve for SchemaRetriever {
             return std::result::Result::Ok(schema.clone());
         }
 
+        if self.load_remote && (base_uri.starts_with("http://") || base_uri.starts_with("https://"))
+        {
+            return self.retrieve_remote(base_uri);
+        }
+
         std::result::Result::Err(format!("Schema not found: {}", uri).into())
     }
 }
 
+/// A user-registered custom format, either a pre-compiled regex (the fast
+/// path, evaluated entirely in Rust) or a JS callback invoked per value.
+enum FormatChecker {
+    Regex(Regex),
+    Callback(JsFormatCallback),
+}
+
+impl FormatChecker {
+    fn check(&self, value: &str) -> bool {
+        match self {
+            FormatChecker::Regex(re) => re.is_match(value),
+            FormatChecker::Callback(cb) => cb.call(value),
+        }
+    }
+}
+
+/// Holds a persistent reference to a JS format validator function so it can
+/// be invoked directly and synchronously from inside `jsonschema`'s format
+/// evaluation. `compile`/`validate`/etc. are all plain synchronous NAPI
+/// calls - never dispatched onto a worker pool - so this is always invoked
+/// from the same JS thread that registered it via `addFormat`, and calling
+/// straight through `Env`/`JsFunction` is safe. A `ThreadsafeFunction` would
+/// be the wrong tool here: its call is delivered by posting a task onto the
+/// JS event loop, which can't be pumped while this very call is blocked
+/// waiting on it, i.e. it would deadlock every synchronous caller.
+struct JsFormatCallback {
+    env: Env,
+    callback: Ref<()>,
+}
+
+// Safety: every call site is reached only via a synchronous NAPI method
+// invoked on the JS thread that created this callback, so `env` and
+// `callback` are never touched from another thread.
+unsafe impl Send for JsFormatCallback {}
+unsafe impl Sync for JsFormatCallback {}
+
+impl JsFormatCallback {
+    fn call(&self, value: &str) -> bool {
+        let func = match self.env.get_reference_value::<JsFunction>(&self.callback) {
+            Ok(func) => func,
+            Err(_) => return false,
+        };
+
+        let arg = match self.env.create_string(value) {
+            Ok(arg) => arg,
+            Err(_) => return false,
+        };
+
+        match func.call(None, &[arg]) {
+            Ok(result) => result
+                .coerce_to_bool()
+                .and_then(|b| b.get_value())
+                .unwrap_or(false),
+            Err(_) => false,
+        }
+    }
+}
+
+impl Drop for JsFormatCallback {
+    fn drop(&mut self) {
+        let _ = self.callback.unref(self.env);
+    }
+}
+
+/// Constructor options for `Ajv`. `loadRemote` opts into fetching
+/// unregistered `http(s)` `$ref`s at compile time; `allowedHosts` is the
+/// allowlist those fetches are restricted to (required when `loadRemote` is
+/// on, since the allowlist is empty and therefore denies everything by
+/// default).
+#[napi(object)]
+pub struct AjvOptions {
+    pub load_remote: Option<bool>,
+    pub allowed_hosts: Option<Vec<String>>,
+}
+
+/// A schema-store-style association: documents whose `$schema` equals `url`,
+/// or whose filename matches one of `file_match`'s globs, are validated with
+/// the schema registered under `schema_key` (see `Ajv::add_schema`).
+#[napi(object)]
+pub struct CatalogEntry {
+    pub url: String,
+    pub file_match: Option<Vec<String>>,
+    pub schema_key: String,
+}
+
+/// Resolved form of `CatalogEntry` with the file-match globs pre-compiled,
+/// since the same catalog is matched against every document passed to
+/// `validateFor`.
+struct CatalogAssociation {
+    url: String,
+    file_match: Vec<Regex>,
+    schema_key: String,
+}
+
+/// A `Validator` compiled for a catalog entry, cached by `schema_key` so
+/// repeated `compileFor`/`validateFor` calls for the same entry reuse the
+/// compiled validation tree instead of rebuilding it.
+struct CompiledCatalogEntry {
+    validator: Arc<Validator>,
+    schema: Arc<Value>,
+    base_uri: Option<String>,
+    default_output_format: String,
+}
+
 #[napi]
 pub struct Ajv {
     schemas: Arc<HashMap<String, Value>>,
+    formats: HashMap<String, Arc<FormatChecker>>,
+    load_remote: bool,
+    allowed_hosts: Arc<Vec<String>>,
+    remote_cache: Arc<Mutex<HashMap<String, Value>>>,
+    catalog: Vec<CatalogAssociation>,
+    compiled_catalog: Mutex<HashMap<String, Arc<CompiledCatalogEntry>>>,
 }
 
 #[napi]
 impl Ajv {
     #[napi(constructor)]
-    pub fn new() -> Self {
+    pub fn new(options: Option<AjvOptions>) -> Self {
+        let options = options.unwrap_or(AjvOptions {
+            load_remote: None,
+            allowed_hosts: None,
+        });
+
         Ajv {
             schemas: Arc::new(HashMap::new()),
+            formats: HashMap::new(),
+            load_remote: options.load_remote.unwrap_or(false),
+            allowed_hosts: Arc::new(options.allowed_hosts.unwrap_or_default()),
+            remote_cache: Arc::new(Mutex::new(HashMap::new())),
+            catalog: Vec::new(),
+            compiled_catalog: Mutex::new(HashMap::new()),
         }
     }
 
+    /// Register a `$schema`/filename association for `validateFor`/`compileFor`.
+    #[napi]
+    pub fn add_catalog_entry(&mut self, entry: CatalogEntry) -> Result<()> {
+        let file_match = entry
+            .file_match
+            .unwrap_or_default()
+            .iter()
+            .map(|pattern| glob_to_regex(pattern))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e: regex::Error| Error::from_reason(format!("Invalid fileMatch pattern: {}", e)))?;
+
+        self.catalog.push(CatalogAssociation {
+            url: entry.url,
+            file_match,
+            schema_key: entry.schema_key,
+        });
+        Ok(())
+    }
+
+    /// Resolve and compile the catalog entry whose `fileMatch` globs match
+    /// `name` (a filename), caching the compiled validator by schema key.
+    /// No document is passed here, so `$schema` matching applies only in
+    /// `validateFor`.
+    #[napi(js_name = "compileFor")]
+    pub fn compile_for(&self, name: Option<String>) -> Result<NapiValidator> {
+        self.compile_for_impl(name.as_deref(), None)
+    }
+
+    /// Like `compileFor`, but also validates `data` against the resolved
+    /// schema in one call.
+    #[napi(js_name = "validateFor")]
+    pub fn validate_for(
+        &self,
+        name: Option<String>,
+        data: serde_json::Value,
+    ) -> Result<ValidationResult> {
+        let validator = self.compile_for_impl(name.as_deref(), Some(&data))?;
+        validator.validate_impl(&data)
+    }
+
+    fn resolve_catalog_schema_key(&self, name: Option<&str>, data: Option<&Value>) -> Option<String> {
+        if let Some(schema_url) = data.and_then(|d| d.get("$schema")).and_then(|v| v.as_str()) {
+            if let Some(entry) = self.catalog.iter().find(|e| e.url == schema_url) {
+                return Some(entry.schema_key.clone());
+            }
+        }
+
+        if let Some(name) = name {
+            if let Some(entry) = self
+                .catalog
+                .iter()
+                .find(|e| e.file_match.iter().any(|re| re.is_match(name)))
+            {
+                return Some(entry.schema_key.clone());
+            }
+        }
+
+        None
+    }
+
+    fn compile_for_impl(&self, name: Option<&str>, data: Option<&Value>) -> Result<NapiValidator> {
+        let schema_key = self
+            .resolve_catalog_schema_key(name, data)
+            .ok_or_else(|| Error::from_reason("No catalog entry matches the given document or filename"))?;
+
+        if let Some(cached) = self.compiled_catalog.lock().unwrap().get(&schema_key) {
+            return Ok(NapiValidator {
+                validator: Arc::clone(&cached.validator),
+                schema: Arc::clone(&cached.schema),
+                base_uri: cached.base_uri.clone(),
+                default_output_format: cached.default_output_format.clone(),
+            });
+        }
+
+        let schema = self
+            .schemas
+            .get(&schema_key)
+            .cloned()
+            .ok_or_else(|| Error::from_reason(format!("Catalog schema key not registered: {}", schema_key)))?;
+
+        let validator = self.compile(schema, None, None, None)?;
+
+        self.compiled_catalog.lock().unwrap().insert(
+            schema_key,
+            Arc::new(CompiledCatalogEntry {
+                validator: Arc::clone(&validator.validator),
+                schema: Arc::clone(&validator.schema),
+                base_uri: validator.base_uri.clone(),
+                default_output_format: validator.default_output_format.clone(),
+            }),
+        );
+
+        Ok(validator)
+    }
+
+    /// Register a custom string format. Pass `pattern` to check values with a
+    /// pre-compiled regex (fast path, no JS boundary crossing); otherwise
+    /// `validator` is called with the string value on each check and a
+    /// truthy return is treated as valid.
+    #[napi]
+    pub fn add_format(
+        &mut self,
+        env: Env,
+        name: String,
+        validator: Option<JsFunction>,
+        pattern: Option<String>,
+    ) -> Result<()> {
+        let checker = if let Some(pattern) = pattern {
+            // Anchor at both ends: like the JS-callback branch, `format` must
+            // judge the whole value, not just find the pattern somewhere
+            // inside it (unlike JSON Schema's `pattern`, which is documented
+            // as unanchored).
+            let re = Regex::new(&format!("^(?:{})$", pattern))
+                .map_err(|e| Error::from_reason(format!("Invalid format pattern: {}", e)))?;
+            FormatChecker::Regex(re)
+        } else if let Some(validator) = validator {
+            let callback = env.create_reference(validator)?;
+            FormatChecker::Callback(JsFormatCallback { env, callback })
+        } else {
+            return Err(Error::from_reason(
+                "add_format requires either a validator callback or a regex pattern",
+            ));
+        };
+
+        self.formats.insert(name, Arc::new(checker));
+        Ok(())
+    }
+
     #[napi]
     pub fn add_schema(&mut self, schema: serde_json::Value, key: Option<String>) -> Result<()> {
         let id = if let Some(k) = key {
@@ -81,19 +760,74 @@ impl Ajv {
 
         // Get mutable access to the inner HashMap
         let schemas = Arc::make_mut(&mut self.schemas);
-        schemas.insert(id, schema);
+        schemas.insert(id.clone(), schema);
+
+        // A catalog validator already compiled for this key was built from
+        // the schema we just replaced; drop it so the next `compileFor`/
+        // `validateFor` rebuilds against the new one instead of silently
+        // keeping on validating against the stale tree.
+        self.compiled_catalog.lock().unwrap().remove(&id);
+
         Ok(())
     }
 
     #[napi]
     pub fn compile(
         &self,
-        schema: serde_json::Value,
+        mut schema: serde_json::Value,
         draft_uri: Option<String>,
+        output_format: Option<String>,
+        validate_schema: Option<Either<bool, String>>,
     ) -> Result<NapiValidator> {
-        // Create retriever with registered schemas
+        // "true" (default), "false", or "log" - mirrors JS Ajv's validateSchema.
+        let validate_schema_mode = match validate_schema {
+            None => "true".to_string(),
+            Some(Either::A(enabled)) => if enabled { "true" } else { "false" }.to_string(),
+            Some(Either::B(mode)) => mode,
+        };
+
+        let openapi_mode = draft_uri.as_deref() == Some("openapi-3.0");
+
+        // OpenAPI 3.0 documents use keywords (`nullable`, `discriminator`)
+        // that no JSON Schema meta-schema knows about, so meta-validation is
+        // skipped in OpenAPI mode; the rewritten schema is not meta-validated
+        // either.
+        if validate_schema_mode != "false" && !openapi_mode {
+            let draft_for_meta_check = draft_uri.as_deref().and_then(parse_draft_uri);
+            if let Err(meta_errors) = validate_against_meta_schema(&schema, draft_for_meta_check) {
+                // Structured, not a single opaque string: each error keeps its
+                // own message/instancePath/schemaPath so JS callers can
+                // `JSON.parse` the thrown error's message for path-annotated
+                // diagnostics, the same shape instance validation returns.
+                let errors_json = serde_json::to_string(
+                    &meta_errors
+                        .iter()
+                        .map(|e| {
+                            serde_json::json!({
+                                "message": e.message,
+                                "instancePath": e.instance_path,
+                                "schemaPath": e.schema_path,
+                            })
+                        })
+                        .collect::<Vec<_>>(),
+                )
+                .unwrap_or_default();
+
+                if validate_schema_mode == "log" {
+                    eprintln!("[ajv] schema does not match its meta-schema: {}", errors_json);
+                } else {
+                    return Err(Error::from_reason(errors_json));
+                }
+            }
+        }
+
+        // Create retriever with registered schemas, plus an opt-in remote
+        // fallback for unregistered http(s) $refs.
         let retriever = SchemaRetriever {
             schemas: Arc::clone(&self.schemas),
+            load_remote: self.load_remote,
+            allowed_hosts: Arc::clone(&self.allowed_hosts),
+            remote_cache: Arc::clone(&self.remote_cache),
         };
 
         // Enable format validation to pass strict Ajv tests
@@ -102,30 +836,45 @@ impl Ajv {
             .with_retriever(retriever)
             .should_validate_formats(true);
 
-        if let Some(uri) = draft_uri {
-            if let Some(draft) = match uri.as_str() {
-                "http://json-schema.org/draft-04/schema#" => Some(Draft::Draft4),
-                "http://json-schema.org/draft-06/schema#" => Some(Draft::Draft6),
-                "http://json-schema.org/draft-07/schema#" => Some(Draft::Draft7),
-                "https://json-schema.org/draft/2019-09/schema" => Some(Draft::Draft201909),
-                "https://json-schema.org/draft/2020-12/schema" => Some(Draft::Draft202012),
-                _ => None,
-            } {
-                options = options.with_draft(draft);
-            }
+        if openapi_mode {
+            // OpenAPI 3.0 schemas are Draft 4 plus `nullable`/`discriminator`;
+            // rewrite those into plain JSON Schema before building.
+            apply_openapi_dialect(&mut schema);
+            options = options.with_draft(Draft::Draft4);
+        } else if let Some(draft) = draft_uri.as_deref().and_then(parse_draft_uri) {
+            options = options.with_draft(draft);
+        }
+
+        for (name, checker) in self.formats.iter() {
+            let checker = Arc::clone(checker);
+            options = options.with_format(name.clone(), move |value: &str| checker.check(value));
         }
 
         let validator = options
             .build(&schema)
             .map_err(|e| Error::from_reason(e.to_string()))?;
 
-        Ok(NapiValidator { validator })
+        let base_uri = schema
+            .get("$id")
+            .or_else(|| schema.get("id"))
+            .and_then(|v| v.as_str())
+            .map(|s| s.trim_end_matches('#').to_string());
+
+        Ok(NapiValidator {
+            validator: Arc::new(validator),
+            schema: Arc::new(schema),
+            base_uri,
+            default_output_format: output_format.unwrap_or_else(|| "basic".to_string()),
+        })
     }
 }
 
 #[napi]
 pub struct NapiValidator {
-    validator: Validator,
+    validator: Arc<Validator>,
+    schema: Arc<Value>,
+    base_uri: Option<String>,
+    default_output_format: String,
 }
 
 #[napi(object)]
@@ -141,6 +890,27 @@ pub struct AjvError {
     pub schema_path: String,
 }
 
+/// A single node of a structured validation output, following the shape of
+/// the JSON Schema "basic"/"detailed"/"verbose" output formats.
+#[napi(object)]
+pub struct OutputUnit {
+    pub valid: bool,
+    pub keyword_location: String,
+    pub instance_location: String,
+    pub absolute_keyword_location: Option<String>,
+    pub error: Option<String>,
+    pub errors: Option<Vec<OutputUnit>>,
+}
+
+/// Structured validation output selectable via `outputFormat` at compile
+/// time or per-call: "flag" (valid only), "basic" (flat error list), or
+/// "detailed"/"verbose" (errors nested by schema location).
+#[napi(object)]
+pub struct ValidationOutput {
+    pub valid: bool,
+    pub errors: Option<Vec<OutputUnit>>,
+}
+
 #[napi]
 impl NapiValidator {
     #[napi]
@@ -196,6 +966,144 @@ impl NapiValidator {
         Ok(self.validator.is_valid(&data))
     }
 
+    /// Validate and return structured output in the schema's "flag", "basic",
+    /// "detailed" or "verbose" format. Defaults to the format selected at
+    /// `compile` time when `format` is omitted.
+    #[napi(js_name = "validateOutput")]
+    pub fn validate_output(
+        &self,
+        data: serde_json::Value,
+        format: Option<String>,
+    ) -> Result<ValidationOutput> {
+        let format = format.unwrap_or_else(|| self.default_output_format.clone());
+        self.validate_output_impl(&data, &format)
+    }
+
+    fn absolute_keyword_location(&self, keyword_location: &str) -> Option<String> {
+        self.base_uri
+            .as_ref()
+            .map(|base| format!("{}#{}", base, keyword_location.trim_start_matches('#')))
+    }
+
+    fn basic_units(&self, data: &Value) -> Vec<OutputUnit> {
+        self.validator
+            .iter_errors(data)
+            .map(|e| {
+                let keyword_location = e.schema_path.to_string();
+                let instance_location = e.instance_path.to_string();
+                let absolute_keyword_location = self.absolute_keyword_location(&keyword_location);
+                OutputUnit {
+                    valid: false,
+                    keyword_location,
+                    instance_location,
+                    absolute_keyword_location,
+                    error: Some(e.to_string()),
+                    errors: None,
+                }
+            })
+            .collect()
+    }
+
+    fn validate_output_impl(&self, data: &Value, format: &str) -> Result<ValidationOutput> {
+        let valid = self.validator.is_valid(data);
+
+        if valid || format == "flag" {
+            return Ok(ValidationOutput { valid, errors: None });
+        }
+
+        let units = self.basic_units(data);
+
+        match format {
+            "detailed" => Ok(ValidationOutput {
+                valid: false,
+                errors: Some(self.nest_basic_units(units, false)),
+            }),
+            "verbose" => Ok(ValidationOutput {
+                valid: false,
+                errors: Some(self.nest_basic_units(units, true)),
+            }),
+            // "basic" and any unrecognized format fall back to the flat list.
+            _ => Ok(ValidationOutput {
+                valid: false,
+                errors: Some(units),
+            }),
+        }
+    }
+
+    /// Group flat basic units by their parent schema location so siblings
+    /// under the same keyword are nested together, approximating the
+    /// applicator tree that "detailed"/"verbose" describe. When
+    /// `annotate_passing` is set (verbose), subschema locations under the
+    /// schema's `properties`/`items`/`oneOf`/`anyOf`/`allOf`/`if`-`then`-`else`
+    /// applicators that produced no error are added as passing (`valid:
+    /// true`) siblings, so verbose output reflects branches that were
+    /// evaluated and matched, not just the ones that failed.
+    fn nest_basic_units(&self, units: Vec<OutputUnit>, annotate_passing: bool) -> Vec<OutputUnit> {
+        let mut grouped: Vec<(String, Vec<OutputUnit>)> = Vec::new();
+        for unit in units {
+            let parent = unit
+                .keyword_location
+                .rsplit_once('/')
+                .map(|(parent, _)| parent.to_string())
+                .unwrap_or_default();
+
+            if let Some((_, children)) = grouped.iter_mut().find(|(loc, _)| *loc == parent) {
+                children.push(unit);
+            } else {
+                grouped.push((parent, vec![unit]));
+            }
+        }
+
+        if annotate_passing {
+            let mut applicator_locations = Vec::new();
+            collect_applicator_locations(&self.schema, String::new(), &mut applicator_locations);
+
+            for location in applicator_locations {
+                // A failing unit's group is keyed by the *parent* of its
+                // keyword location (e.g. `/properties/foo/type` groups under
+                // `/properties/foo`), so an applicator that failed shows up
+                // as a group keyed by its own location - check for that
+                // directly rather than by the applicator's own parent. A
+                // group keyed below it means a descendant failed, so it is
+                // not reported as passing either.
+                let descendant_prefix = format!("{}/", location);
+                let already_covered = grouped
+                    .iter()
+                    .any(|(loc, _)| *loc == location || loc.starts_with(&descendant_prefix));
+                if already_covered {
+                    continue;
+                }
+
+                let passing = OutputUnit {
+                    valid: true,
+                    keyword_location: location.clone(),
+                    instance_location: String::new(),
+                    absolute_keyword_location: None,
+                    error: None,
+                    errors: None,
+                };
+
+                grouped.push((location, vec![passing]));
+            }
+        }
+
+        grouped
+            .into_iter()
+            .map(|(parent, children)| {
+                let absolute_keyword_location = self.absolute_keyword_location(&parent);
+                let valid = children.iter().all(|c| c.valid);
+                OutputUnit {
+                    valid,
+                    keyword_location: parent,
+                    instance_location: children[0].instance_location.clone(),
+                    absolute_keyword_location,
+                    error: None,
+                    errors: Some(children),
+                }
+            })
+            .collect()
+    }
+
     fn validate_impl(&self, data: &Value) -> Result<ValidationResult> {
         if self.validator.is_valid(data) {
             Ok(ValidationResult {
@@ -220,3 +1128,151 @@ impl NapiValidator {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile_openapi(schema: Value) -> Validator {
+        let mut schema = schema;
+        apply_openapi_dialect(&mut schema);
+        jsonschema::options()
+            .with_draft(Draft::Draft4)
+            .build(&schema)
+            .expect("schema should compile")
+    }
+
+    fn discriminator_schema() -> Value {
+        serde_json::json!({
+            "discriminator": {"propertyName": "petType"},
+            "oneOf": [
+                {"$ref": "#/$defs/Dog"},
+                {"$ref": "#/$defs/Cat"},
+            ],
+            "$defs": {
+                "Dog": {
+                    "type": "object",
+                    "properties": {"petType": {"const": "Dog"}, "bark": {"type": "boolean"}},
+                    "required": ["petType", "bark"],
+                },
+                "Cat": {
+                    "type": "object",
+                    "properties": {"petType": {"const": "Cat"}, "meow": {"type": "boolean"}},
+                    "required": ["petType", "meow"],
+                },
+            },
+        })
+    }
+
+    #[test]
+    fn discriminator_dispatches_to_the_named_variant() {
+        let validator = compile_openapi(discriminator_schema());
+        assert!(validator.is_valid(&serde_json::json!({"petType": "Dog", "bark": true})));
+        assert!(validator.is_valid(&serde_json::json!({"petType": "Cat", "meow": true})));
+        // Wrong shape for the selected branch must still fail.
+        assert!(!validator.is_valid(&serde_json::json!({"petType": "Dog", "meow": true})));
+    }
+
+    #[test]
+    fn discriminator_rejects_unmatched_value() {
+        let validator = compile_openapi(discriminator_schema());
+        assert!(!validator.is_valid(&serde_json::json!({"petType": "Bird"})));
+    }
+
+    #[test]
+    fn discriminator_keeps_inline_variants_disjunctive() {
+        let schema = serde_json::json!({
+            "discriminator": {"propertyName": "petType"},
+            "oneOf": [
+                {"$ref": "#/$defs/Dog"},
+                {"type": "object", "properties": {"nickname": {"type": "string"}}, "required": ["nickname"]},
+            ],
+            "$defs": {
+                "Dog": {
+                    "type": "object",
+                    "properties": {"petType": {"const": "Dog"}, "bark": {"type": "boolean"}},
+                    "required": ["petType", "bark"],
+                },
+            },
+        });
+        let validator = compile_openapi(schema);
+
+        // Matches the named branch.
+        assert!(validator.is_valid(&serde_json::json!({"petType": "Dog", "bark": true})));
+        // Matches the inline variant, which has no discriminator value at all.
+        assert!(validator.is_valid(&serde_json::json!({"nickname": "Rex"})));
+        // Matches neither.
+        assert!(!validator.is_valid(&serde_json::json!({"petType": "Bird"})));
+    }
+
+    #[test]
+    fn format_pattern_is_anchored_to_the_whole_value() {
+        let re = Regex::new(&format!("^(?:{})$", r"\d{5}")).unwrap();
+        let checker = FormatChecker::Regex(re);
+        assert!(checker.check("12345"));
+        assert!(!checker.check("12345x"));
+        assert!(!checker.check("abc12345xyz"));
+    }
+
+    fn napi_validator(schema: Value) -> NapiValidator {
+        let validator = jsonschema::options()
+            .build(&schema)
+            .expect("schema should compile");
+        NapiValidator {
+            validator: Arc::new(validator),
+            schema: Arc::new(schema),
+            base_uri: None,
+            default_output_format: "basic".to_string(),
+        }
+    }
+
+    #[test]
+    fn verbose_output_annotates_passing_siblings_detailed_does_not() {
+        let schema = serde_json::json!({
+            "properties": {
+                "foo": {"type": "string"},
+                "bar": {"type": "number"},
+            },
+        });
+        let napi_validator = napi_validator(schema);
+        let data = serde_json::json!({"foo": 123, "bar": 5});
+
+        let detailed = napi_validator
+            .validate_output_impl(&data, "detailed")
+            .unwrap();
+        let verbose = napi_validator
+            .validate_output_impl(&data, "verbose")
+            .unwrap();
+
+        let detailed_units = detailed.errors.unwrap();
+        let verbose_units = verbose.errors.unwrap();
+
+        // Only the failing "foo" branch is present in "detailed".
+        assert_eq!(detailed_units.len(), 1);
+        assert_eq!(detailed_units[0].keyword_location, "/properties/foo");
+
+        // "verbose" additionally reports the passing "bar" branch.
+        assert_eq!(verbose_units.len(), 2);
+        let bar_unit = verbose_units
+            .iter()
+            .find(|u| u.keyword_location == "/properties/bar")
+            .expect("passing bar branch should be annotated in verbose output");
+        assert!(bar_unit.valid);
+    }
+
+    #[test]
+    fn basic_output_is_a_flat_error_list() {
+        let schema = serde_json::json!({
+            "properties": {
+                "foo": {"type": "string"},
+            },
+        });
+        let napi_validator = napi_validator(schema);
+        let data = serde_json::json!({"foo": 123});
+
+        let basic = napi_validator.validate_output_impl(&data, "basic").unwrap();
+        let units = basic.errors.unwrap();
+        assert_eq!(units.len(), 1);
+        assert_eq!(units[0].keyword_location, "/properties/foo/type");
+    }
+}